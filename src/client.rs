@@ -1,12 +1,33 @@
 //! Clients for high level interactions with TUF repositories.
 
+use std::io::Read;
+
+use chrono::{DateTime, Utc};
+
 use Result;
 use crypto;
+use crypto::PublicKey;
 use error::Error;
 use interchange::DataInterchange;
-use metadata::{MetadataVersion, RootMetadata, Role, MetadataPath};
+use metadata::{
+    Delegations, Metadata, MetadataVersion, RootMetadata, Role, MetadataPath, SnapshotMetadata,
+    TargetDescription, TargetPath, TargetsMetadata, TimestampMetadata,
+};
 use repository::Repository;
 use tuf::Tuf;
+use util::SafeReader;
+
+/// Whether a delegation search should keep scanning sibling delegations after a nested
+/// delegation lookup came back `Error::NotFound`. A `terminating` delegation ends the search of
+/// its branch instead of falling through to siblings.
+fn continue_past_not_found(terminating: bool) -> bool {
+    !terminating
+}
+
+/// Whether `expires` is in the past relative to now.
+fn is_expired(expires: &DateTime<Utc>) -> bool {
+    expires < &Utc::now()
+}
 
 /// A client that interacts with TUF repositories.
 pub struct Client<D, L, R>
@@ -38,6 +59,45 @@ where
         }
     }
 
+    /// Create a new TUF client that trusts the given `root_keys` to sign the root metadata at
+    /// `root_version`, rather than requiring a pre-verified `Tuf` up front.
+    ///
+    /// At least `root_threshold` of `root_keys` must have signed that root metadata; the keys
+    /// need not appear inside the root metadata itself. Once that initial root is accepted, the
+    /// normal root-chain walk in `update_root` takes over, so this is a safe way for a tool like
+    /// a self-updater to bootstrap trust from keys it pins at build time.
+    pub fn with_trusted_root_keys(
+        config: Config,
+        root_version: MetadataVersion,
+        root_threshold: u32,
+        root_keys: &[PublicKey],
+        local: L,
+        mut remote: R,
+    ) -> Result<Self> {
+        let root_path = MetadataPath::from_role(&Role::Root);
+        let signed_root = remote.fetch_metadata(
+            &root_path,
+            &root_version,
+            &config.max_root_size,
+            None,
+        )?;
+        Self::check_expiration(
+            &root_path,
+            &D::deserialize::<RootMetadata>(signed_root.unverified_signed())?,
+        )?;
+
+        let mut tuf = Tuf::from_trusted_root_keys(root_threshold, root_keys, signed_root)?;
+
+        Self::update_root(&mut tuf, &mut remote, &config.max_root_size)?;
+
+        Ok(Client {
+            tuf: tuf,
+            config: config,
+            local: local,
+            remote: remote,
+        })
+    }
+
     /// Update TUF metadata from the local repository.
     ///
     /// Returns `true` if an update occurred and `false` otherwise.
@@ -96,19 +156,32 @@ where
         )
     }
 
+    /// Returns `Error::ExpiredMetadata(path)` if `metadata` identified by `path` has expired.
+    fn check_expiration<M: Metadata>(path: &MetadataPath, metadata: &M) -> Result<()> {
+        if is_expired(metadata.expires()) {
+            Err(Error::ExpiredMetadata(path.clone()))
+        } else {
+            Ok(())
+        }
+    }
+
     /// Returns `true` if an update occurred and `false` otherwise.
     fn update_root<T>(tuf: &mut Tuf<D>, repo: &mut T, max_root_size: &Option<usize>) -> Result<bool>
     where
         T: Repository<D>,
     {
+        let root_path = MetadataPath::from_role(&Role::Root);
+
         let latest_root = repo.fetch_metadata(
-            &Role::Root,
+            &root_path,
             &MetadataVersion::None,
             max_root_size,
             None,
         )?;
-        let latest_version = D::deserialize::<RootMetadata>(latest_root.unverified_signed())?
-            .version();
+        let latest_root_metadata =
+            D::deserialize::<RootMetadata>(latest_root.unverified_signed())?;
+        Self::check_expiration(&root_path, &latest_root_metadata)?;
+        let latest_version = latest_root_metadata.version();
 
         if latest_version < tuf.root().version() {
             return Err(Error::VerificationFailure(format!(
@@ -123,9 +196,12 @@ where
         let err_msg = "TUF claimed no update occurred when one should have. \
                        This is a programming error. Please report this as a bug.";
 
+        // Intermediate root versions on the way to `latest_version` are only a stepping stone to
+        // verify the chain of trust; they are routinely expired by the time they're superseded,
+        // so only the final, latest root is held to the expiration check above.
         for i in (tuf.root().version() + 1)..latest_version {
             let signed = repo.fetch_metadata(
-                &Role::Root,
+                &root_path,
                 &MetadataVersion::Number(i),
                 max_root_size,
                 None,
@@ -152,12 +228,18 @@ where
     where
         T: Repository<D>,
     {
+        let timestamp_path = MetadataPath::from_role(&Role::Timestamp);
+
         let ts = repo.fetch_metadata(
-            &Role::Timestamp,
+            &timestamp_path,
             &MetadataVersion::None,
             max_timestamp_size,
             None,
         )?;
+        Self::check_expiration(
+            &timestamp_path,
+            &D::deserialize::<TimestampMetadata>(ts.unverified_signed())?,
+        )?;
         tuf.update_timestamp(ts)
     }
 
@@ -186,12 +268,18 @@ where
             None => None,
         };
 
+        let snapshot_path = MetadataPath::from_role(&Role::Snapshot);
+
         let snap = repo.fetch_metadata(
-            &Role::Snapshot,
+            &snapshot_path,
             &MetadataVersion::None,
             &snapshot_description.length(),
             hashes,
         )?;
+        Self::check_expiration(
+            &snapshot_path,
+            &D::deserialize::<SnapshotMetadata>(snap.unverified_signed())?,
+        )?;
         tuf.update_snapshot(snap)
     }
 
@@ -220,14 +308,150 @@ where
             None => None,
         };
 
+        let targets_path = MetadataPath::from_role(&Role::Targets);
+
         let targets = repo.fetch_metadata(
-            &Role::Targets,
+            &targets_path,
             &MetadataVersion::None,
             &targets_description.length(),
             hashes,
         )?;
+        Self::check_expiration(
+            &targets_path,
+            &D::deserialize::<TargetsMetadata>(targets.unverified_signed())?,
+        )?;
         tuf.update_targets(targets)
     }
+
+    /// Fetch a target from the remote repository, verifying its length and hash(es) as the
+    /// returned reader is consumed.
+    ///
+    /// If `target` is not described by the top-level targets metadata, the delegation tree
+    /// rooted there is walked pre-order in search of a delegation authorized for it.
+    pub fn fetch_target(&mut self, target: &TargetPath) -> Result<impl Read> {
+        let description = Self::target_description(&mut self.tuf, &mut self.remote, target)?;
+        Self::fetch_target_with_description(&mut self.remote, target, description)
+    }
+
+    /// Look up the `TargetDescription` for `target`, following delegations if it is not
+    /// present in the top-level targets metadata.
+    fn target_description<T>(
+        tuf: &mut Tuf<D>,
+        repo: &mut T,
+        target: &TargetPath,
+    ) -> Result<TargetDescription>
+    where
+        T: Repository<D>,
+    {
+        let targets = tuf.targets()
+            .ok_or_else(|| Error::MissingMetadata(Role::Targets))?
+            .clone();
+
+        if let Some(d) = targets.targets().get(target) {
+            return Ok(d.clone());
+        }
+
+        match targets.delegations() {
+            Some(delegations) => Self::walk_delegations(tuf, repo, delegations.clone(), target),
+            None => Err(Error::NotFound),
+        }
+    }
+
+    /// Pre-order walk of a delegation tree in search of `target`, fetching and verifying (and
+    /// caching in `tuf`) each delegated targets metadata the first time it is needed.
+    ///
+    /// A delegation marked terminating that does not contain `target` ends the search of that
+    /// branch with `Error::NotFound` rather than falling through to sibling delegations.
+    fn walk_delegations<T>(
+        tuf: &mut Tuf<D>,
+        repo: &mut T,
+        delegations: Delegations,
+        target: &TargetPath,
+    ) -> Result<TargetDescription>
+    where
+        T: Repository<D>,
+    {
+        for delegation in delegations.roles() {
+            if !delegation.paths().iter().any(|path| path.matches(target)) {
+                continue;
+            }
+
+            let role = delegation.role().clone();
+
+            if !tuf.delegated_targets().contains_key(&role) {
+                // A delegated role that snapshot doesn't describe has no pinned length or
+                // hash to bound its fetch against, so it can't be safely fetched at all.
+                let description = tuf.snapshot()
+                    .ok_or_else(|| Error::MissingMetadata(Role::Snapshot))?
+                    .meta()
+                    .get(&role)
+                    .cloned()
+                    .ok_or_else(|| {
+                        Error::VerificationFailure(format!(
+                            "Snapshot metadata did not contain a description of the \
+                             delegated targets role {:?}.",
+                            role
+                        ))
+                    })?;
+
+                let hashes = match description.hashes() {
+                    Some(hashes) => Some(crypto::hash_preference(hashes)?),
+                    None => None,
+                };
+                let max_size = description.length();
+
+                let signed = repo.fetch_metadata(&role, &MetadataVersion::None, &max_size, hashes)?;
+                Self::check_expiration(
+                    &role,
+                    &D::deserialize::<TargetsMetadata>(signed.unverified_signed())?,
+                )?;
+                tuf.update_delegated_targets(&role, delegation, delegations.keys(), signed)?;
+            }
+
+            let delegated = tuf.delegated_targets()
+                .get(&role)
+                .expect("delegated targets metadata was just verified and cached")
+                .clone();
+
+            if let Some(d) = delegated.targets().get(target) {
+                return Ok(d.clone());
+            }
+
+            match delegated.delegations() {
+                Some(nested) => match Self::walk_delegations(tuf, repo, nested.clone(), target) {
+                    Ok(d) => return Ok(d),
+                    Err(Error::NotFound) if continue_past_not_found(delegation.terminating()) => {
+                        continue
+                    }
+                    Err(e) => return Err(e),
+                },
+                None if delegation.terminating() => return Err(Error::NotFound),
+                None => continue,
+            }
+        }
+
+        Err(Error::NotFound)
+    }
+
+    /// Fetch `target` from `repo` and wrap it in a reader that enforces the length and
+    /// hash(es) recorded in `description`, erroring rather than yielding unverified bytes if
+    /// either check fails.
+    fn fetch_target_with_description<T>(
+        repo: &mut T,
+        target: &TargetPath,
+        description: TargetDescription,
+    ) -> Result<impl Read>
+    where
+        T: Repository<D>,
+    {
+        let hashes = match description.hashes() {
+            Some(hashes) => Some(crypto::hash_preference(hashes)?),
+            None => None,
+        };
+
+        let read = repo.fetch_target(target, &description.length(), hashes)?;
+        SafeReader::new(read, description)
+    }
 }
 
 /// Configuration for a TUF `Client`.
@@ -291,3 +515,113 @@ impl Default for ConfigBuilder {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::HashMap;
+    use std::io::Cursor;
+
+    use chrono::Duration;
+    use crypto::{HashAlgorithm, HashValue};
+    use interchange::Json;
+    use metadata::SignedMetadata;
+
+    struct MockRepo {
+        target_body: Vec<u8>,
+    }
+
+    impl Repository<Json> for MockRepo {
+        fn fetch_metadata(
+            &mut self,
+            _meta_path: &MetadataPath,
+            _version: &MetadataVersion,
+            _max_size: &Option<usize>,
+            _hash_data: Option<(&'static HashAlgorithm, HashValue)>,
+        ) -> Result<SignedMetadata<Json>> {
+            unreachable!("not exercised by these tests")
+        }
+
+        fn fetch_target(
+            &mut self,
+            _target: &TargetPath,
+            _max_size: &Option<usize>,
+            _hash_data: Option<(&'static HashAlgorithm, HashValue)>,
+        ) -> Result<Box<Read>> {
+            Ok(Box::new(Cursor::new(self.target_body.clone())))
+        }
+    }
+
+    fn fetch(body: &[u8], description: TargetDescription) -> Result<Vec<u8>> {
+        let mut repo = MockRepo {
+            target_body: body.to_vec(),
+        };
+        let target = TargetPath::new("some/target".to_string())?;
+
+        let mut read = Client::<Json, MockRepo, MockRepo>::fetch_target_with_description(
+            &mut repo,
+            &target,
+            description,
+        )?;
+
+        let mut buf = Vec::new();
+        read.read_to_end(&mut buf)
+            .map_err(|e| Error::Generic(e.to_string()))?;
+        Ok(buf)
+    }
+
+    #[test]
+    fn terminating_delegation_stops_the_search() {
+        assert!(!continue_past_not_found(true));
+    }
+
+    #[test]
+    fn non_terminating_delegation_falls_through_to_siblings() {
+        assert!(continue_past_not_found(false));
+    }
+
+    #[test]
+    fn is_expired_is_true_for_a_timestamp_in_the_past() {
+        assert!(is_expired(&(Utc::now() - Duration::seconds(1))));
+    }
+
+    #[test]
+    fn is_expired_is_false_for_a_timestamp_in_the_future() {
+        assert!(!is_expired(&(Utc::now() + Duration::minutes(1))));
+    }
+
+    #[test]
+    fn fetch_target_rejects_a_length_mismatch() {
+        let body = b"this body is longer than the description claims";
+        let description = TargetDescription::new(Some(4), None);
+
+        assert!(fetch(body, description).is_err());
+    }
+
+    #[test]
+    fn fetch_target_rejects_a_hash_mismatch() {
+        let body = b"the real target body";
+        let mut hashes = HashMap::new();
+        hashes.insert(
+            HashAlgorithm::Sha256,
+            crypto::calculate_hash(b"not the real target body", &HashAlgorithm::Sha256),
+        );
+        let description = TargetDescription::new(Some(body.len()), Some(hashes));
+
+        assert!(fetch(body, description).is_err());
+    }
+
+    #[test]
+    fn fetch_target_accepts_a_matching_length_and_hash() {
+        let body = b"the real target body";
+        let mut hashes = HashMap::new();
+        hashes.insert(
+            HashAlgorithm::Sha256,
+            crypto::calculate_hash(body, &HashAlgorithm::Sha256),
+        );
+        let description = TargetDescription::new(Some(body.len()), Some(hashes));
+
+        assert_eq!(fetch(body, description).unwrap(), body.to_vec());
+    }
+}
@@ -0,0 +1,363 @@
+//! Repositories that TUF metadata and targets can be fetched from.
+
+use std::io::Read;
+use std::marker::PhantomData;
+
+use Result;
+use crypto;
+use crypto::{HashAlgorithm, HashValue};
+use error::Error;
+use interchange::DataInterchange;
+use metadata::{MetadataPath, MetadataVersion, SignedMetadata, TargetPath};
+
+/// Return whichever of `requested` and `cap` is more restrictive (a present cap always wins
+/// over an absent, i.e. unbounded, request).
+fn clamp(requested: &Option<usize>, cap: &Option<usize>) -> Option<usize> {
+    match (*requested, *cap) {
+        (Some(r), Some(c)) => Some(r.min(c)),
+        (Some(r), None) => Some(r),
+        (None, Some(c)) => Some(c),
+        (None, None) => None,
+    }
+}
+
+/// Join `base_uri`, an optional path `prefix`, and `components` with `/`.
+fn build_uri(base_uri: &str, prefix: Option<&str>, components: &[String]) -> String {
+    let mut uri = base_uri.to_string();
+    if let Some(prefix) = prefix {
+        uri.push('/');
+        uri.push_str(prefix);
+    }
+    for component in components {
+        uri.push('/');
+        uri.push_str(component);
+    }
+    uri
+}
+
+/// A place TUF metadata and targets can be fetched from.
+pub trait Repository<D>
+where
+    D: DataInterchange,
+{
+    /// Fetch signed metadata identified by `meta_path` at `version`, refusing to read more
+    /// than `max_size` bytes and, if given, checking the result against `hash_data`.
+    fn fetch_metadata(
+        &mut self,
+        meta_path: &MetadataPath,
+        version: &MetadataVersion,
+        max_size: &Option<usize>,
+        hash_data: Option<(&'static HashAlgorithm, HashValue)>,
+    ) -> Result<SignedMetadata<D>>;
+
+    /// Fetch the raw bytes of `target`, refusing to read more than `max_size` bytes. If given,
+    /// `hash_data` selects the hash-prefixed file name of a consistent snapshot; the returned
+    /// bytes are not checked against it here.
+    ///
+    /// Callers are responsible for verifying the returned bytes against the target's
+    /// `TargetDescription`; see `Client::fetch_target`.
+    fn fetch_target(
+        &mut self,
+        target: &TargetPath,
+        max_size: &Option<usize>,
+        hash_data: Option<(&'static HashAlgorithm, HashValue)>,
+    ) -> Result<Box<Read>>;
+}
+
+/// A minimal HTTP client abstraction so `HttpRepository` isn't tied to one HTTP library.
+///
+/// Implementations should follow redirects and must not transparently decompress or otherwise
+/// alter the response body, since every byte is subject to hash verification.
+pub trait HttpClient {
+    /// Issue a GET request against `url`, sending `user_agent` as the `User-Agent` header, and
+    /// return a reader over the (successful) response body.
+    fn get(&self, url: &str, user_agent: &str) -> Result<Box<Read>>;
+}
+
+/// A `Repository` that fetches metadata and targets over HTTP.
+pub struct HttpRepository<C, D>
+where
+    C: HttpClient,
+    D: DataInterchange,
+{
+    client: C,
+    base_uri: String,
+    user_agent: String,
+    metadata_prefix: Option<String>,
+    max_root_size: Option<usize>,
+    max_timestamp_size: Option<usize>,
+    interchange: PhantomData<D>,
+}
+
+impl<C, D> HttpRepository<C, D>
+where
+    C: HttpClient,
+    D: DataInterchange,
+{
+    /// Start building an `HttpRepository` rooted at `base_uri` using `client` to make requests.
+    pub fn builder(base_uri: String, client: C) -> HttpRepositoryBuilder<C, D> {
+        HttpRepositoryBuilder::new(base_uri, client)
+    }
+
+    /// Build the URI for a metadata file, which is the only thing `metadata_prefix` applies to.
+    fn metadata_uri(&self, components: &[String]) -> String {
+        build_uri(&self.base_uri, self.metadata_prefix.as_ref().map(String::as_str), components)
+    }
+
+    /// Build the URI for a target file. Targets commonly live under a different path than
+    /// metadata, so this deliberately does not apply `metadata_prefix`.
+    fn target_uri(&self, components: &[String]) -> String {
+        build_uri(&self.base_uri, None, components)
+    }
+
+    fn get(&self, uri: String, max_size: &Option<usize>) -> Result<Box<Read>> {
+        let read = self.client.get(&uri, &self.user_agent)?;
+        Ok(match *max_size {
+            Some(max_size) => Box::new(read.take(max_size as u64)),
+            None => read,
+        })
+    }
+}
+
+impl<C, D> Repository<D> for HttpRepository<C, D>
+where
+    C: HttpClient,
+    D: DataInterchange,
+{
+    fn fetch_metadata(
+        &mut self,
+        meta_path: &MetadataPath,
+        version: &MetadataVersion,
+        max_size: &Option<usize>,
+        hash_data: Option<(&'static HashAlgorithm, HashValue)>,
+    ) -> Result<SignedMetadata<D>> {
+        let file_name = match (version, hash_data) {
+            (&MetadataVersion::Number(n), _) => {
+                format!("{}.{}.{}", n, meta_path.to_raw(), D::extension())
+            }
+            (&MetadataVersion::None, Some((_, ref hash))) => {
+                format!("{}.{}.{}", hash.to_hex(), meta_path.to_raw(), D::extension())
+            }
+            (&MetadataVersion::None, None) => {
+                format!("{}.{}", meta_path.to_raw(), D::extension())
+            }
+        };
+
+        // Never trust a caller-supplied `max_size` larger than the hard cap for root and
+        // timestamp metadata; a malicious server must not be able to use an overly permissive
+        // caller request to exhaust memory during the initial, unverified fetch.
+        let capped_size = match meta_path.to_raw().as_str() {
+            "root" => clamp(max_size, &self.max_root_size),
+            "timestamp" => clamp(max_size, &self.max_timestamp_size),
+            _ => *max_size,
+        };
+
+        let mut read = self.get(self.metadata_uri(&[file_name]), &capped_size)?;
+        let mut buf = Vec::new();
+        read.read_to_end(&mut buf)
+            .map_err(|e| Error::Generic(e.to_string()))?;
+
+        if let Some((alg, ref expected)) = hash_data {
+            let actual = crypto::calculate_hash(&buf, alg);
+            if &actual != expected {
+                return Err(Error::VerificationFailure(format!(
+                    "Downloaded metadata's {:?} hash did not match the expected value",
+                    alg
+                )));
+            }
+        }
+
+        D::from_reader(&*buf)
+    }
+
+    fn fetch_target(
+        &mut self,
+        target: &TargetPath,
+        max_size: &Option<usize>,
+        hash_data: Option<(&'static HashAlgorithm, HashValue)>,
+    ) -> Result<Box<Read>> {
+        let file_name = match hash_data {
+            Some((_, ref hash)) => format!("{}.{}", hash.to_hex(), target.to_raw()),
+            None => target.to_raw(),
+        };
+
+        self.get(self.target_uri(&[file_name]), max_size)
+    }
+}
+
+/// Helper for building and validating an `HttpRepository`.
+pub struct HttpRepositoryBuilder<C, D>
+where
+    C: HttpClient,
+    D: DataInterchange,
+{
+    base_uri: String,
+    client: C,
+    user_agent: Option<String>,
+    metadata_prefix: Option<String>,
+    max_root_size: Option<usize>,
+    max_timestamp_size: Option<usize>,
+    interchange: PhantomData<D>,
+}
+
+impl<C, D> HttpRepositoryBuilder<C, D>
+where
+    C: HttpClient,
+    D: DataInterchange,
+{
+    /// Create a new builder that will fetch from `base_uri` using `client`.
+    pub fn new(base_uri: String, client: C) -> Self {
+        HttpRepositoryBuilder {
+            base_uri: base_uri,
+            client: client,
+            user_agent: None,
+            metadata_prefix: None,
+            max_root_size: Some(1024 * 1024),
+            max_timestamp_size: Some(32 * 1024),
+            interchange: PhantomData,
+        }
+    }
+
+    /// Set the `User-Agent` header sent with every request.
+    pub fn user_agent<S>(mut self, user_agent: S) -> Self
+    where
+        S: Into<String>,
+    {
+        self.user_agent = Some(user_agent.into());
+        self
+    }
+
+    /// Set a path prefix inserted between `base_uri` and the metadata file name, for
+    /// repositories that don't serve metadata from their root.
+    pub fn metadata_prefix<S>(mut self, prefix: S) -> Self
+    where
+        S: Into<String>,
+    {
+        self.metadata_prefix = Some(prefix.into());
+        self
+    }
+
+    /// Set the hard cap on the number of bytes read for root metadata.
+    pub fn max_root_size(mut self, max: Option<usize>) -> Self {
+        self.max_root_size = max;
+        self
+    }
+
+    /// Set the hard cap on the number of bytes read for timestamp metadata.
+    pub fn max_timestamp_size(mut self, max: Option<usize>) -> Self {
+        self.max_timestamp_size = max;
+        self
+    }
+
+    /// Validate this builder and return an `HttpRepository`.
+    pub fn finish(self) -> Result<HttpRepository<C, D>> {
+        if self.base_uri.is_empty() {
+            return Err(Error::Generic("HTTP repository base URI may not be empty".into()));
+        }
+
+        Ok(HttpRepository {
+            client: self.client,
+            base_uri: self.base_uri,
+            user_agent: self.user_agent.unwrap_or_else(|| {
+                format!("rust-tuf/{}", env!("CARGO_PKG_VERSION"))
+            }),
+            metadata_prefix: self.metadata_prefix,
+            max_root_size: self.max_root_size,
+            max_timestamp_size: self.max_timestamp_size,
+            interchange: PhantomData,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::Cursor;
+
+    use interchange::Json;
+    use metadata::Role;
+
+    #[test]
+    fn clamp_prefers_the_smaller_of_request_and_cap() {
+        assert_eq!(clamp(&Some(100), &Some(10)), Some(10));
+        assert_eq!(clamp(&Some(10), &Some(100)), Some(10));
+    }
+
+    #[test]
+    fn clamp_falls_back_to_whichever_side_is_present() {
+        assert_eq!(clamp(&Some(10), &None), Some(10));
+        assert_eq!(clamp(&None, &Some(10)), Some(10));
+        assert_eq!(clamp(&None, &None), None);
+    }
+
+    struct TestClient {
+        body: Vec<u8>,
+    }
+
+    impl HttpClient for TestClient {
+        fn get(&self, _url: &str, _user_agent: &str) -> Result<Box<Read>> {
+            Ok(Box::new(Cursor::new(self.body.clone())))
+        }
+    }
+
+    fn repo(body: &[u8], prefix: Option<&str>) -> HttpRepository<TestClient, Json> {
+        let mut builder = HttpRepository::builder(
+            "http://example.com".to_string(),
+            TestClient { body: body.to_vec() },
+        );
+        if let Some(prefix) = prefix {
+            builder = builder.metadata_prefix(prefix);
+        }
+        builder.finish().unwrap()
+    }
+
+    #[test]
+    fn fetch_metadata_rejects_a_hash_mismatch() {
+        let body = br#"{"signed":{},"signatures":[]}"#;
+        let mut repo = repo(body, None);
+
+        let wrong_hash = crypto::calculate_hash(b"not the body", &HashAlgorithm::Sha256);
+        let err = repo.fetch_metadata(
+            &MetadataPath::from_role(&Role::Snapshot),
+            &MetadataVersion::None,
+            &None,
+            Some((&HashAlgorithm::Sha256, wrong_hash)),
+        ).unwrap_err();
+
+        match err {
+            Error::VerificationFailure(_) => (),
+            e => panic!("expected a verification failure, got {:?}", e),
+        }
+    }
+
+    #[test]
+    fn fetch_metadata_accepts_a_matching_hash() {
+        let body = br#"{"signed":{},"signatures":[]}"#;
+        let mut repo = repo(body, None);
+
+        let hash = crypto::calculate_hash(body, &HashAlgorithm::Sha256);
+        assert!(
+            repo.fetch_metadata(
+                &MetadataPath::from_role(&Role::Snapshot),
+                &MetadataVersion::None,
+                &None,
+                Some((&HashAlgorithm::Sha256, hash)),
+            ).is_ok()
+        );
+    }
+
+    #[test]
+    fn target_uri_never_applies_the_metadata_prefix() {
+        let repo = repo(b"", Some("metadata"));
+
+        assert_eq!(
+            repo.target_uri(&["a/b".to_string()]),
+            "http://example.com/a/b"
+        );
+        assert_eq!(
+            repo.metadata_uri(&["root.json".to_string()]),
+            "http://example.com/metadata/root.json"
+        );
+    }
+}
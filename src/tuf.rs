@@ -0,0 +1,221 @@
+//! Interfaces for interacting with trusted TUF metadata.
+
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::ops::Deref;
+
+use Result;
+use crypto;
+use crypto::{KeyId, PublicKey};
+use interchange::DataInterchange;
+use metadata::{
+    Delegation, MetadataPath, RootMetadata, Role, SignedMetadata, SnapshotMetadata,
+    TargetsMetadata, TimestampMetadata,
+};
+
+/// Metadata that `Tuf` holds as trusted. Apart from the root given to `Tuf::new`, it has passed
+/// signature threshold verification, and an update must also carry a newer version. `Tuf` itself
+/// does not check expiration; `Client` checks it before passing metadata to `Tuf`.
+#[derive(Clone, Debug)]
+pub struct Verified<M> {
+    metadata: M,
+}
+
+impl<M> Deref for Verified<M> {
+    type Target = M;
+
+    fn deref(&self) -> &M {
+        &self.metadata
+    }
+}
+
+/// The trusted metadata database for a single TUF repository.
+pub struct Tuf<D>
+where
+    D: DataInterchange,
+{
+    root: Verified<RootMetadata>,
+    timestamp: Option<Verified<TimestampMetadata>>,
+    snapshot: Option<Verified<SnapshotMetadata>>,
+    targets: Option<Verified<TargetsMetadata>>,
+    delegated_targets: HashMap<MetadataPath, Verified<TargetsMetadata>>,
+    _interchange: PhantomData<D>,
+}
+
+impl<D> Tuf<D>
+where
+    D: DataInterchange,
+{
+    /// Create a new trust database from root metadata that is already known to be trustworthy,
+    /// e.g. loaded from a local copy that was verified on a previous run.
+    pub fn new(root: RootMetadata) -> Self {
+        Tuf {
+            root: Verified { metadata: root },
+            timestamp: None,
+            snapshot: None,
+            targets: None,
+            delegated_targets: HashMap::new(),
+            _interchange: PhantomData,
+        }
+    }
+
+    /// Bootstrap a trust database from `signed_root`, requiring it to be signed by at least
+    /// `root_threshold` of `root_keys` rather than by keys already listed inside the root
+    /// metadata itself.
+    pub fn from_trusted_root_keys(
+        root_threshold: u32,
+        root_keys: &[PublicKey],
+        signed_root: SignedMetadata<D>,
+    ) -> Result<Self> {
+        let keys = root_keys
+            .iter()
+            .map(|k| (k.key_id().clone(), k.clone()))
+            .collect::<HashMap<KeyId, PublicKey>>();
+
+        crypto::verify_signatures(&keys, root_threshold, &signed_root)?;
+
+        let root = D::deserialize::<RootMetadata>(signed_root.unverified_signed())?;
+        Ok(Tuf::new(root))
+    }
+
+    /// The current trusted root metadata.
+    pub fn root(&self) -> &RootMetadata {
+        &self.root
+    }
+
+    /// The current trusted timestamp metadata, if any has been fetched yet.
+    pub fn timestamp(&self) -> Option<&TimestampMetadata> {
+        self.timestamp.as_ref().map(|v| v.deref())
+    }
+
+    /// The current trusted snapshot metadata, if any has been fetched yet.
+    pub fn snapshot(&self) -> Option<&SnapshotMetadata> {
+        self.snapshot.as_ref().map(|v| v.deref())
+    }
+
+    /// The current trusted top-level targets metadata, if any has been fetched yet.
+    pub fn targets(&self) -> Option<&TargetsMetadata> {
+        self.targets.as_ref().map(|v| v.deref())
+    }
+
+    /// Already-fetched, already-verified delegated targets metadata, keyed by the
+    /// `MetadataPath` of the delegated role.
+    pub fn delegated_targets(&self) -> &HashMap<MetadataPath, Verified<TargetsMetadata>> {
+        &self.delegated_targets
+    }
+
+    /// Verify and, if newer, install `signed` as the current root metadata.
+    ///
+    /// Returns `true` if an update occurred and `false` otherwise.
+    pub fn update_root(&mut self, signed: SignedMetadata<D>) -> Result<bool> {
+        crypto::verify_signatures(&self.root.keys(), self.root.root_threshold(), &signed)?;
+
+        let root = D::deserialize::<RootMetadata>(signed.unverified_signed())?;
+        if root.version() <= self.root.version() {
+            return Ok(false);
+        }
+
+        self.root = Verified { metadata: root };
+        Ok(true)
+    }
+
+    /// Verify and, if newer, install `signed` as the current timestamp metadata.
+    ///
+    /// Returns `true` if an update occurred and `false` otherwise.
+    pub fn update_timestamp(&mut self, signed: SignedMetadata<D>) -> Result<bool> {
+        crypto::verify_signatures(
+            &self.root.keys_for(&Role::Timestamp),
+            self.root.threshold_for(&Role::Timestamp),
+            &signed,
+        )?;
+
+        let timestamp = D::deserialize::<TimestampMetadata>(signed.unverified_signed())?;
+        if let Some(ref current) = self.timestamp {
+            if timestamp.version() <= current.version() {
+                return Ok(false);
+            }
+        }
+
+        self.timestamp = Some(Verified { metadata: timestamp });
+        Ok(true)
+    }
+
+    /// Verify and, if newer, install `signed` as the current snapshot metadata.
+    ///
+    /// Returns `true` if an update occurred and `false` otherwise.
+    pub fn update_snapshot(&mut self, signed: SignedMetadata<D>) -> Result<bool> {
+        crypto::verify_signatures(
+            &self.root.keys_for(&Role::Snapshot),
+            self.root.threshold_for(&Role::Snapshot),
+            &signed,
+        )?;
+
+        let snapshot = D::deserialize::<SnapshotMetadata>(signed.unverified_signed())?;
+        if let Some(ref current) = self.snapshot {
+            if snapshot.version() <= current.version() {
+                return Ok(false);
+            }
+        }
+
+        self.snapshot = Some(Verified { metadata: snapshot });
+        Ok(true)
+    }
+
+    /// Verify and, if newer, install `signed` as the current top-level targets metadata.
+    ///
+    /// Returns `true` if an update occurred and `false` otherwise.
+    pub fn update_targets(&mut self, signed: SignedMetadata<D>) -> Result<bool> {
+        crypto::verify_signatures(
+            &self.root.keys_for(&Role::Targets),
+            self.root.threshold_for(&Role::Targets),
+            &signed,
+        )?;
+
+        let targets = D::deserialize::<TargetsMetadata>(signed.unverified_signed())?;
+        if let Some(ref current) = self.targets {
+            if targets.version() <= current.version() {
+                return Ok(false);
+            }
+        }
+
+        self.targets = Some(Verified { metadata: targets });
+
+        // A new top-level targets could have revoked a delegation or rotated a delegate's keys,
+        // so previously cached delegated targets can no longer be trusted without being
+        // re-verified against the new delegation it describes.
+        self.delegated_targets.clear();
+
+        Ok(true)
+    }
+
+    /// Verify `signed` against the threshold of `keys` that `delegation` names, then cache it
+    /// as the trusted metadata for the delegated role `role`.
+    ///
+    /// Returns `true` if an update occurred and `false` otherwise.
+    pub fn update_delegated_targets(
+        &mut self,
+        role: &MetadataPath,
+        delegation: &Delegation,
+        keys: &HashMap<KeyId, PublicKey>,
+        signed: SignedMetadata<D>,
+    ) -> Result<bool> {
+        let delegation_keys = delegation
+            .key_ids()
+            .iter()
+            .filter_map(|id| keys.get(id).map(|key| (id.clone(), key.clone())))
+            .collect::<HashMap<KeyId, PublicKey>>();
+
+        crypto::verify_signatures(&delegation_keys, delegation.threshold(), &signed)?;
+
+        let targets = D::deserialize::<TargetsMetadata>(signed.unverified_signed())?;
+        if let Some(current) = self.delegated_targets.get(role) {
+            if targets.version() <= current.version() {
+                return Ok(false);
+            }
+        }
+
+        self.delegated_targets
+            .insert(role.clone(), Verified { metadata: targets });
+        Ok(true)
+    }
+}